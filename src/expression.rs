@@ -2,6 +2,19 @@ use crate::LicenseReq;
 use smallvec::SmallVec;
 use std::fmt;
 
+/// The largest number of distinct requirements [`Expression::minimize`] will
+/// build a `2^n` truth table for. Past this the table itself, and the
+/// Quine-McCluskey combining step over it, become too expensive to build.
+const MAX_MINIMIZE_VARS: usize = 16;
+
+/// The largest number of satisfying assignments (minterms)
+/// [`Expression::minimize`] will run the Quine-McCluskey combining step
+/// over. This is a separate guard from [`MAX_MINIMIZE_VARS`] because the
+/// combining step's cost depends on how many minterms there are, not just
+/// on the variable count, eg a straight `OR` chain of `MAX_MINIMIZE_VARS`
+/// distinct licenses satisfies almost every assignment.
+const MAX_MINIMIZE_MINTERMS: usize = 4096;
+
 /// A license requirement inside an SPDX license expression, including
 /// the span in the expression where it is located
 #[derive(Debug, Clone)]
@@ -128,6 +141,348 @@ impl Expression {
             Ok(())
         }
     }
+
+    /// Produces an equivalent expression in a boolean-minimal form, collapsing
+    /// redundant terms, eg `MIT AND MIT` becomes `MIT`, and
+    /// `MIT OR (MIT AND Apache-2.0)` becomes `MIT`.
+    ///
+    /// This works by collecting the distinct [`LicenseReq`]s in the
+    /// expression, treating it as a boolean function over them, and running
+    /// the [Quine-McCluskey algorithm](https://en.wikipedia.org/wiki/Quine%E2%80%93McCluskey_algorithm)
+    /// to find a minimal sum-of-products form.
+    ///
+    /// If the expression references more than [`MAX_MINIMIZE_VARS`] distinct
+    /// requirements, or ends up with more than [`MAX_MINIMIZE_MINTERMS`]
+    /// satisfying assignments, building or combining the truth table would
+    /// be too expensive, so the original expression is returned unchanged.
+    /// The same is true if the expression is a tautology (always satisfied),
+    /// since there is no SPDX syntax for an unconditional "true".
+    pub fn minimize(&self) -> Expression {
+        let mut reqs: Vec<LicenseReq> = Vec::new();
+
+        for er in self.requirements() {
+            if !reqs.iter().any(|r| r == &er.req) {
+                reqs.push(er.req.clone());
+            }
+        }
+
+        let n = reqs.len();
+        if n == 0 || n > MAX_MINIMIZE_VARS {
+            return self.clone();
+        }
+
+        let num_assignments = 1u32 << n;
+        let mut minterms = Vec::new();
+
+        for assignment in 0..num_assignments {
+            let satisfied = self.evaluate(|req| {
+                let idx = reqs.iter().position(|r| r == req).unwrap();
+                (assignment >> idx) & 1 == 1
+            });
+
+            if satisfied {
+                minterms.push(assignment);
+            }
+        }
+
+        if minterms.len() as u32 == num_assignments || minterms.len() > MAX_MINIMIZE_MINTERMS {
+            return self.clone();
+        }
+
+        let primes = prime_implicants(&minterms, n);
+        let cover = minimal_cover(&primes, &minterms);
+
+        let mut expr = SmallVec::<[ExprNode; 5]>::new();
+
+        for (i, term) in cover.iter().enumerate() {
+            let bits: Vec<usize> = term
+                .bits
+                .iter()
+                .enumerate()
+                .filter_map(|(idx, b)| if *b == Some(true) { Some(idx) } else { None })
+                .collect();
+
+            for (j, &bit) in bits.iter().enumerate() {
+                expr.push(ExprNode::Req(ExpressionReq {
+                    req: reqs[bit].clone(),
+                    span: 0..0,
+                }));
+
+                if j > 0 {
+                    expr.push(ExprNode::Op(Operator::And));
+                }
+            }
+
+            if i > 0 {
+                expr.push(ExprNode::Op(Operator::Or));
+            }
+        }
+
+        let original = render_canonical(&expr);
+        Expression { expr, original }
+    }
+
+    /// Returns true if `self` and `other` are logically equivalent, ie they
+    /// evaluate identically for every possible combination of satisfied
+    /// requirements, even if they aren't [`PartialEq`].
+    ///
+    /// This is different from `==`, which only compares the parsed postfix
+    /// form node-by-node, so eg `MIT OR Apache-2.0` is not `==` to
+    /// `Apache-2.0 OR MIT`, nor is `MIT AND MIT` `==` to `MIT`, even though
+    /// both pairs are `equivalent`.
+    ///
+    /// If the two expressions reference more than 20 distinct requirements
+    /// between them, the `2^n` truth table needed would be too large to
+    /// build, so this falls back to the cheaper syntactic `==` comparison.
+    pub fn equivalent(&self, other: &Expression) -> bool {
+        let mut reqs: Vec<LicenseReq> = Vec::new();
+
+        for er in self.requirements().chain(other.requirements()) {
+            if !reqs.iter().any(|r| r == &er.req) {
+                reqs.push(er.req.clone());
+            }
+        }
+
+        let n = reqs.len();
+        if n > 20 {
+            return self == other;
+        }
+
+        for assignment in 0..(1u32 << n) {
+            let lhs = self.evaluate(|req| {
+                let idx = reqs.iter().position(|r| r == req).unwrap();
+                (assignment >> idx) & 1 == 1
+            });
+
+            let rhs = other.evaluate(|req| {
+                let idx = reqs.iter().position(|r| r == req).unwrap();
+                (assignment >> idx) & 1 == 1
+            });
+
+            if lhs != rhs {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Renders the expression from its parsed form back into a normalized
+    /// infix string, rather than just echoing the original input the way
+    /// [`Display`](fmt::Display) does.
+    ///
+    /// Operator spacing and casing are normalized, and parentheses are only
+    /// inserted where SPDX operator precedence (`AND` binds tighter than
+    /// `OR`) actually requires them, so expressions that differ only in
+    /// formatting, eg with extra parentheses, render the same way. Operands
+    /// keep their order and repeated terms are kept, so expressions that are
+    /// only [`equivalent`](Expression::equivalent), eg `MIT OR Apache-2.0`
+    /// and `Apache-2.0 OR MIT`, render differently. This gives a stable
+    /// string suitable for hashing, deduplication, or diffing license fields
+    /// across manifests.
+    pub fn canonical(&self) -> String {
+        render_canonical(&self.expr)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Prec {
+    Or,
+    And,
+    Atom,
+}
+
+/// Renders a postfix `ExprNode` stream back into a normalized infix string,
+/// inserting parentheses only where SPDX operator precedence (`AND` binds
+/// tighter than `OR`) actually requires them. Shared by
+/// [`Expression::canonical`] and [`Expression::minimize`] so that the two
+/// never disagree about how the same tree should be displayed.
+fn render_canonical(expr: &[ExprNode]) -> String {
+    let mut stack: Vec<(String, Prec)> = Vec::new();
+
+    for node in expr {
+        match node {
+            ExprNode::Req(req) => {
+                stack.push((req.req.to_string(), Prec::Atom));
+            }
+            ExprNode::Op(op) => {
+                let (rhs, rhs_prec) = stack.pop().unwrap();
+                let (lhs, lhs_prec) = stack.pop().unwrap();
+
+                let (op_str, prec) = match op {
+                    Operator::And => (" AND ", Prec::And),
+                    Operator::Or => (" OR ", Prec::Or),
+                };
+
+                let lhs = if lhs_prec < prec {
+                    format!("({})", lhs)
+                } else {
+                    lhs
+                };
+
+                let rhs = if rhs_prec < prec {
+                    format!("({})", rhs)
+                } else {
+                    rhs
+                };
+
+                stack.push((format!("{}{}{}", lhs, op_str, rhs), prec));
+            }
+        }
+    }
+
+    stack.pop().map(|(s, _)| s).unwrap_or_default()
+}
+
+/// A partially-specified assignment of the variables used while running the
+/// Quine-McCluskey algorithm: each entry is `Some(bit)` if that variable is
+/// fixed to `bit`, or `None` if it's a don't-care.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Term {
+    bits: Vec<Option<bool>>,
+}
+
+impl Term {
+    fn from_minterm(minterm: u32, n: usize) -> Self {
+        Self {
+            bits: (0..n).map(|i| Some((minterm >> i) & 1 == 1)).collect(),
+        }
+    }
+
+    /// If `self` and `other` differ in exactly one fixed bit, combines them
+    /// into a single term with that bit turned into a don't-care.
+    fn combine(&self, other: &Self) -> Option<Self> {
+        let mut diff = None;
+
+        for (i, (a, b)) in self.bits.iter().zip(other.bits.iter()).enumerate() {
+            if a != b {
+                if diff.is_some() {
+                    return None;
+                }
+
+                diff = Some(i);
+            }
+        }
+
+        let diff = diff?;
+        let mut bits = self.bits.clone();
+        bits[diff] = None;
+
+        Some(Self { bits })
+    }
+
+    /// Returns true if this (possibly don't-care) term matches `minterm`.
+    fn covers(&self, minterm: u32) -> bool {
+        self.bits
+            .iter()
+            .enumerate()
+            .all(|(i, b)| b.map_or(true, |bit| bit == ((minterm >> i) & 1 == 1)))
+    }
+
+    /// The number of bits fixed to `true`. Two terms can only ever combine if
+    /// their popcounts differ by exactly one, so grouping by this lets the
+    /// combining step skip every pair that can't possibly merge.
+    fn popcount(&self) -> usize {
+        self.bits.iter().filter(|b| **b == Some(true)).count()
+    }
+}
+
+/// Runs the Quine-McCluskey combining step to reduce the given minterms down
+/// to the set of prime implicants that cover them.
+///
+/// Terms are grouped by popcount (the standard QM optimization) so that each
+/// round only compares terms in adjacent groups, since two terms can only
+/// combine if they differ in exactly one fixed bit, which requires their
+/// popcounts to differ by exactly one. This avoids the unbucketed `O(M^2)`
+/// comparison of every pair of terms.
+fn prime_implicants(minterms: &[u32], n: usize) -> Vec<Term> {
+    let mut groups: Vec<Vec<Term>> = vec![Vec::new(); n + 1];
+
+    for &m in minterms {
+        let term = Term::from_minterm(m, n);
+        let pc = term.popcount();
+        groups[pc].push(term);
+    }
+
+    let mut primes = Vec::new();
+
+    loop {
+        let mut next_groups: Vec<Vec<Term>> = vec![Vec::new(); n + 1];
+        let mut combined: Vec<Vec<bool>> = groups.iter().map(|g| vec![false; g.len()]).collect();
+
+        for pc in 0..n {
+            for (i, a) in groups[pc].iter().enumerate() {
+                for (j, b) in groups[pc + 1].iter().enumerate() {
+                    if let Some(merged) = a.combine(b) {
+                        combined[pc][i] = true;
+                        combined[pc + 1][j] = true;
+
+                        let bucket = &mut next_groups[merged.popcount()];
+                        if !bucket.contains(&merged) {
+                            bucket.push(merged);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut any_combined = false;
+
+        for (group, was_combined) in groups.iter().zip(combined.iter()) {
+            for (term, combined) in group.iter().zip(was_combined.iter()) {
+                if *combined {
+                    any_combined = true;
+                } else if !primes.contains(term) {
+                    primes.push(term.clone());
+                }
+            }
+        }
+
+        if !any_combined {
+            break;
+        }
+
+        groups = next_groups;
+    }
+
+    primes
+}
+
+/// Greedily selects prime implicants that together cover every minterm,
+/// preferring an implicant that is the only one covering some still-uncovered
+/// minterm (ie an essential prime implicant) before falling back to the one
+/// that covers the most remaining minterms.
+fn minimal_cover(primes: &[Term], minterms: &[u32]) -> Vec<Term> {
+    let mut uncovered: Vec<u32> = minterms.to_vec();
+    let mut selected: Vec<Term> = Vec::new();
+
+    while !uncovered.is_empty() {
+        let essential = uncovered.iter().find_map(|&m| {
+            let mut covering = primes.iter().filter(|p| p.covers(m));
+            let first = covering.next()?;
+            if covering.next().is_none() {
+                Some(first.clone())
+            } else {
+                None
+            }
+        });
+
+        let chosen = essential.unwrap_or_else(|| {
+            primes
+                .iter()
+                .max_by_key(|p| uncovered.iter().filter(|&&m| p.covers(m)).count())
+                .unwrap()
+                .clone()
+        });
+
+        uncovered.retain(|&m| !chosen.covers(m));
+
+        if !selected.contains(&chosen) {
+            selected.push(chosen);
+        }
+    }
+
+    selected
 }
 
 impl AsRef<str> for Expression {
@@ -191,4 +546,62 @@ mod test {
 
         assert_ne!(normal, llvm_exc);
     }
+
+    #[test]
+    fn minimize() {
+        let redundant_and = Expression::parse("MIT AND MIT").unwrap();
+        assert_eq!(redundant_and.minimize().requirements().count(), 1);
+
+        let redundant_or = Expression::parse("MIT OR (MIT AND Apache-2.0)").unwrap();
+        let minimized = redundant_or.minimize();
+        assert_eq!(minimized.requirements().count(), 1);
+        assert_eq!(
+            minimized.requirements().next().unwrap().req.to_string(),
+            "MIT"
+        );
+
+        let already_minimal = Expression::parse("MIT OR Apache-2.0").unwrap();
+        assert_eq!(already_minimal.minimize().requirements().count(), 2);
+
+        // A cover term with more than one literal must still produce a
+        // well-formed, evaluable postfix stream
+        let single_and_term = Expression::parse("Apache-2.0 AND GPL-3.0").unwrap();
+        let minimized = single_and_term.minimize();
+        assert!(minimized.evaluate(|_| true));
+        assert!(!minimized.evaluate(|_| false));
+        assert_eq!(minimized.canonical(), "Apache-2.0 AND GPL-3.0");
+
+        let or_of_and = Expression::parse("MIT OR (Apache-2.0 AND GPL-3.0)").unwrap();
+        let minimized = or_of_and.minimize();
+        assert!(minimized.equivalent(&or_of_and));
+        assert_eq!(minimized.canonical(), "MIT OR Apache-2.0 AND GPL-3.0");
+    }
+
+    #[test]
+    fn equivalent() {
+        let a = Expression::parse("MIT OR Apache-2.0").unwrap();
+        let b = Expression::parse("Apache-2.0 OR MIT").unwrap();
+        assert_ne!(a, b);
+        assert!(a.equivalent(&b));
+
+        let redundant = Expression::parse("MIT AND MIT").unwrap();
+        let mit = Expression::parse("MIT").unwrap();
+        assert_ne!(redundant, mit);
+        assert!(redundant.equivalent(&mit));
+
+        let different = Expression::parse("MIT OR Apache-2.0 WITH LLVM-exception").unwrap();
+        assert!(!a.equivalent(&different));
+    }
+
+    #[test]
+    fn canonical() {
+        let redundant_parens = Expression::parse("(MIT OR (Apache-2.0))").unwrap();
+        assert_eq!(redundant_parens.canonical(), "MIT OR Apache-2.0");
+
+        let needed_parens = Expression::parse("(MIT OR Apache-2.0) AND GPL-3.0").unwrap();
+        assert_eq!(needed_parens.canonical(), "(MIT OR Apache-2.0) AND GPL-3.0");
+
+        let flattened_and = Expression::parse("MIT AND (Apache-2.0 AND GPL-3.0)").unwrap();
+        assert_eq!(flattened_and.canonical(), "MIT AND Apache-2.0 AND GPL-3.0");
+    }
 }
@@ -6,6 +6,72 @@ use crate::{
 };
 use smallvec::SmallVec;
 
+// Operator precedence in SPDX 2.1
+// +
+// WITH
+// AND
+// OR
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Op {
+    //Plus,
+    //With,
+    And,
+    Or,
+    Open,
+}
+
+struct OpAndSpan {
+    op: Op,
+    span: std::ops::Range<usize>,
+}
+
+fn apply_op(op: OpAndSpan, q: &mut SmallVec<[ExprNode; 5]>) {
+    let op = match op.op {
+        Op::And => Operator::And,
+        Op::Or => Operator::Or,
+        _ => unreachable!(),
+    };
+
+    q.push(ExprNode::Op(op));
+}
+
+/// Pops operators of strictly greater precedence off `op_stack` onto
+/// `expr_queue`, then pushes `new_op`, ie one "shift" of the shunting-yard
+/// algorithm.
+fn push_operator(
+    new_op: Op,
+    span: std::ops::Range<usize>,
+    op_stack: &mut SmallVec<[OpAndSpan; 3]>,
+    expr_queue: &mut SmallVec<[ExprNode; 5]>,
+) {
+    while let Some(op) = op_stack.last() {
+        match &op.op {
+            Op::Open => break,
+            top => {
+                if *top < new_op {
+                    let top = op_stack.pop().unwrap();
+                    apply_op(top, expr_queue);
+                } else {
+                    break;
+                }
+            }
+        }
+    }
+
+    op_stack.push(OpAndSpan { op: new_op, span });
+}
+
+fn expected_for(last_token: Option<Token<'_>>) -> &'static [&'static str] {
+    match last_token {
+        None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => &["<license>", "("],
+        Some(Token::CloseParen) => &["AND", "OR"],
+        Some(Token::Exception(_)) => &["AND", "OR", ")"],
+        Some(Token::SPDX(_)) => &["AND", "OR", "WITH", ")", "+"],
+        Some(Token::LicenseRef { .. }) | Some(Token::Plus) => &["AND", "OR", "WITH", ")"],
+        Some(Token::With) => &["<exception>"],
+    }
+}
+
 impl Expression {
     /// Given a license expression, attempts to parse and validate it as a valid SPDX expression
     ///
@@ -17,98 +83,106 @@ impl Expression {
     /// * A license or exception immediately follows another license or exception, without
     /// a valid AND, OR, or WITH operator separating them
     /// * An AND, OR, or WITH doesn't have a license or `)` preceding it
+    ///
+    /// This only ever reports the first problem found. To collect every
+    /// problem in the expression in one pass, use [`Expression::parse_recovering`].
     pub fn parse(original: &str) -> Result<Self, ParseError> {
-        let lexer = Lexer::new(original);
-
-        // Operator precedence in SPDX 2.1
-        // +
-        // WITH
-        // AND
-        // OR
-        #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
-        enum Op {
-            //Plus,
-            //With,
-            And,
-            Or,
-            Open,
-        }
+        Self::parse_recovering(original).map_err(|mut errors| errors.remove(0))
+    }
 
-        struct OpAndSpan {
-            op: Op,
-            span: std::ops::Range<usize>,
-        }
+    /// Like [`Expression::parse`], but instead of stopping at the first
+    /// problem, keeps going and collects every [`ParseError`] found in the
+    /// expression in one pass, recovering locally after each one so later
+    /// mistakes are still reported.
+    ///
+    /// Recovery is best-effort and intentionally simple:
+    /// * A license or `(` immediately following another license, with no
+    /// `AND`/`OR` in between, is treated as though an `AND` had been written.
+    /// * An operator (`AND`, `OR`, `WITH`, `+`, `)`, exception) that has
+    /// nothing valid for it to apply to is skipped.
+    /// * Unbalanced parentheses are recorded as errors, but the rest of the
+    /// expression is still parsed, and the operator stack is drained as if
+    /// the parentheses had matched.
+    ///
+    /// If no problems were found the result is identical to `parse`.
+    pub fn parse_recovering(original: &str) -> Result<Self, Vec<ParseError>> {
+        let lexer = Lexer::new(original);
 
         let mut op_stack = SmallVec::<[OpAndSpan; 3]>::new();
         let mut expr_queue = SmallVec::<[ExprNode; 5]>::new();
+        let mut errors = Vec::new();
 
         // Keep track of the last token to simplify validation of the token stream
         let mut last_token: Option<Token<'_>> = None;
 
-        let apply_op = |op: OpAndSpan, q: &mut SmallVec<[ExprNode; 5]>| {
-            let op = match op.op {
-                Op::And => Operator::And,
-                Op::Or => Operator::Or,
-                _ => unreachable!(),
+        macro_rules! record {
+            ($span:expr, $reason:expr) => {
+                errors.push(ParseError {
+                    original,
+                    span: $span,
+                    reason: $reason,
+                });
             };
+        }
 
-            q.push(ExprNode::Op(op));
-            Ok(())
-        };
-
-        let make_err_for_token = |last_token: Option<Token<'_>>, span: std::ops::Range<usize>| {
-            let expected: &[&str] = match last_token {
-                None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {
-                    &["<license>", "("]
+        // Basic implementation of the https://en.wikipedia.org/wiki/Shunting-yard_algorithm,
+        // extended to recover from errors rather than bailing on the first one
+        'outer: for tok in lexer {
+            let lt = match tok {
+                Ok(lt) => lt,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
                 }
-                Some(Token::CloseParen) => &["AND", "OR"],
-                Some(Token::Exception(_)) => &["AND", "OR", ")"],
-                Some(Token::SPDX(_)) => &["AND", "OR", "WITH", ")", "+"],
-                Some(Token::LicenseRef { .. }) | Some(Token::Plus) => &["AND", "OR", "WITH", ")"],
-                Some(Token::With) => &["<exception>"],
             };
 
-            Err(ParseError {
-                original,
-                span,
-                reason: Reason::Unexpected(&expected),
-            })
-        };
-
-        // Basic implementation of the https://en.wikipedia.org/wiki/Shunting-yard_algorithm
-        'outer: for tok in lexer {
-            let lt = tok?;
             match &lt.token {
-                Token::SPDX(id) => match last_token {
-                    None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {
-                        expr_queue.push(ExprNode::Req(ExpressionReq {
-                            req: LicenseReq {
-                                license: LicenseItem::SPDX {
-                                    id: *id,
-                                    or_later: false,
-                                },
-                                exception: None,
-                            },
-                            span: lt.span.start as u32..lt.span.end as u32,
-                        }));
+                Token::SPDX(id) => {
+                    match last_token {
+                        None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {}
+                        _ => {
+                            record!(
+                                lt.span.clone(),
+                                Reason::Unexpected(expected_for(last_token))
+                            );
+                            push_operator(Op::And, lt.span.clone(), &mut op_stack, &mut expr_queue);
+                        }
                     }
-                    _ => return make_err_for_token(last_token, lt.span),
-                },
-                Token::LicenseRef { doc_ref, lic_ref } => match last_token {
-                    None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {
-                        expr_queue.push(ExprNode::Req(ExpressionReq {
-                            req: LicenseReq {
-                                license: LicenseItem::Other {
-                                    doc_ref: doc_ref.map(String::from),
-                                    lic_ref: String::from(*lic_ref),
-                                },
-                                exception: None,
+
+                    expr_queue.push(ExprNode::Req(ExpressionReq {
+                        req: LicenseReq {
+                            license: LicenseItem::SPDX {
+                                id: *id,
+                                or_later: false,
                             },
-                            span: lt.span.start as u32..lt.span.end as u32,
-                        }));
+                            exception: None,
+                        },
+                        span: lt.span.start as u32..lt.span.end as u32,
+                    }));
+                }
+                Token::LicenseRef { doc_ref, lic_ref } => {
+                    match last_token {
+                        None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {}
+                        _ => {
+                            record!(
+                                lt.span.clone(),
+                                Reason::Unexpected(expected_for(last_token))
+                            );
+                            push_operator(Op::And, lt.span.clone(), &mut op_stack, &mut expr_queue);
+                        }
                     }
-                    _ => return make_err_for_token(last_token, lt.span),
-                },
+
+                    expr_queue.push(ExprNode::Req(ExpressionReq {
+                        req: LicenseReq {
+                            license: LicenseItem::Other {
+                                doc_ref: doc_ref.map(String::from),
+                                lic_ref: String::from(*lic_ref),
+                            },
+                            exception: None,
+                        },
+                        span: lt.span.start as u32..lt.span.end as u32,
+                    }));
+                }
                 Token::Plus => match last_token {
                     Some(Token::SPDX(_)) => match expr_queue.last_mut().unwrap() {
                         ExprNode::Req(ExpressionReq {
@@ -123,11 +197,23 @@ impl Expression {
                         }
                         _ => unreachable!(),
                     },
-                    _ => return make_err_for_token(last_token, lt.span),
+                    _ => {
+                        record!(
+                            lt.span.clone(),
+                            Reason::Unexpected(expected_for(last_token))
+                        );
+                        continue;
+                    }
                 },
                 Token::With => match last_token {
                     Some(Token::SPDX(_)) | Some(Token::LicenseRef { .. }) | Some(Token::Plus) => {}
-                    _ => return make_err_for_token(last_token, lt.span),
+                    _ => {
+                        record!(
+                            lt.span.clone(),
+                            Reason::Unexpected(expected_for(last_token))
+                        );
+                        continue;
+                    }
                 },
                 Token::Or | Token::And => match last_token {
                     Some(Token::SPDX(_))
@@ -141,69 +227,68 @@ impl Expression {
                             _ => unreachable!(),
                         };
 
-                        while let Some(op) = op_stack.last() {
-                            match &op.op {
-                                Op::Open => break,
-                                top => {
-                                    if *top < new_op {
-                                        let top = op_stack.pop().unwrap();
-
-                                        match top.op {
-                                            Op::And | Op::Or => apply_op(top, &mut expr_queue)?,
-                                            _ => unreachable!(),
-                                        }
-                                    } else {
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-
-                        op_stack.push(OpAndSpan {
-                            op: new_op,
-                            span: lt.span,
-                        });
+                        push_operator(new_op, lt.span.clone(), &mut op_stack, &mut expr_queue);
                     }
-                    _ => return make_err_for_token(last_token, lt.span),
-                },
-                Token::OpenParen => match last_token {
-                    None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {
-                        op_stack.push(OpAndSpan {
-                            op: Op::Open,
-                            span: lt.span,
-                        });
+                    _ => {
+                        record!(
+                            lt.span.clone(),
+                            Reason::Unexpected(expected_for(last_token))
+                        );
+                        continue;
                     }
-                    _ => return make_err_for_token(last_token, lt.span),
                 },
-                Token::CloseParen => {
+                Token::OpenParen => {
                     match last_token {
-                        Some(Token::SPDX(_))
-                        | Some(Token::LicenseRef { .. })
-                        | Some(Token::Plus)
-                        | Some(Token::Exception(_))
-                        | Some(Token::CloseParen) => {
-                            while let Some(top) = op_stack.pop() {
-                                match top.op {
-                                    Op::And | Op::Or => apply_op(top, &mut expr_queue)?,
-                                    Op::Open => {
-                                        // This is the only place we go back to the top of the outer loop,
-                                        // so make sure we correctly record this token
-                                        last_token = Some(Token::CloseParen);
-                                        continue 'outer;
-                                    }
+                        None | Some(Token::And) | Some(Token::Or) | Some(Token::OpenParen) => {}
+                        _ => {
+                            record!(
+                                lt.span.clone(),
+                                Reason::Unexpected(expected_for(last_token))
+                            );
+                            push_operator(Op::And, lt.span.clone(), &mut op_stack, &mut expr_queue);
+                        }
+                    }
+
+                    op_stack.push(OpAndSpan {
+                        op: Op::Open,
+                        span: lt.span.clone(),
+                    });
+                }
+                Token::CloseParen => match last_token {
+                    Some(Token::SPDX(_))
+                    | Some(Token::LicenseRef { .. })
+                    | Some(Token::Plus)
+                    | Some(Token::Exception(_))
+                    | Some(Token::CloseParen) => {
+                        let mut found_open = false;
+
+                        while let Some(top) = op_stack.pop() {
+                            match top.op {
+                                Op::And | Op::Or => apply_op(top, &mut expr_queue),
+                                Op::Open => {
+                                    found_open = true;
+                                    break;
                                 }
                             }
+                        }
 
-                            // We didn't have an opening parentheses if we get here
-                            return Err(ParseError {
-                                original,
-                                span: lt.span,
-                                reason: Reason::UnopenedParens,
-                            });
+                        if !found_open {
+                            // We didn't have an opening parentheses, record it but keep going,
+                            // with every operator on the stack already applied
+                            record!(lt.span.clone(), Reason::UnopenedParens);
                         }
-                        _ => return make_err_for_token(last_token, lt.span),
+
+                        last_token = Some(Token::CloseParen);
+                        continue 'outer;
                     }
-                }
+                    _ => {
+                        record!(
+                            lt.span.clone(),
+                            Reason::Unexpected(expected_for(last_token))
+                        );
+                        continue;
+                    }
+                },
                 Token::Exception(exc) => match last_token {
                     Some(Token::With) => match expr_queue.last_mut() {
                         Some(ExprNode::Req(lic)) => {
@@ -211,7 +296,13 @@ impl Expression {
                         }
                         _ => unreachable!(),
                     },
-                    _ => return make_err_for_token(last_token, lt.span),
+                    _ => {
+                        record!(
+                            lt.span.clone(),
+                            Reason::Unexpected(expected_for(last_token))
+                        );
+                        continue;
+                    }
                 },
             }
 
@@ -227,30 +318,28 @@ impl Expression {
             | Some(Token::Plus) => {}
             // We have to have at least one valid license requirement
             None => {
-                return Err(ParseError {
-                    original,
-                    span: 0..original.len(),
-                    reason: Reason::Empty,
-                });
+                record!(0..original.len(), Reason::Empty);
+            }
+            _ => {
+                let end = original.len()..original.len();
+                record!(end, Reason::Unexpected(expected_for(last_token)));
             }
-            _ => return make_err_for_token(last_token, original.len()..original.len()),
         }
 
+        // Drain whatever is left on the operator stack, recording but not
+        // stopping for any parentheses that were never closed
         while let Some(top) = op_stack.pop() {
             match top.op {
-                Op::And | Op::Or => apply_op(top, &mut expr_queue)?,
+                Op::And | Op::Or => apply_op(top, &mut expr_queue),
                 Op::Open => {
-                    return Err(ParseError {
-                        original,
-                        span: top.span,
-                        reason: Reason::UnclosedParens,
-                    });
+                    record!(top.span, Reason::UnclosedParens);
                 }
             }
         }
 
-        // TODO: Investigate using https://github.com/oli-obk/quine-mc_cluskey to simplify
-        // expressions, but not really critical. Just cool.
+        if !errors.is_empty() {
+            return Err(errors);
+        }
 
         Ok(Expression {
             original: original.to_owned(),
@@ -258,3 +347,34 @@ impl Expression {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Expression;
+
+    #[test]
+    fn recovers_missing_operator() {
+        let errors = Expression::parse_recovering("MIT Apache-2.0").unwrap_err();
+        assert_eq!(errors.len(), 1);
+
+        // The recovered expression is not returned, so parse still fails,
+        // unlike the same input written with an explicit AND
+        let with_and = Expression::parse("MIT AND Apache-2.0").unwrap();
+        let recovered = Expression::parse("MIT Apache-2.0");
+        assert!(recovered.is_err());
+        assert_eq!(with_and.requirements().count(), 2);
+    }
+
+    #[test]
+    fn collects_multiple_errors() {
+        let errors = Expression::parse_recovering("MIT AND AND Apache-2.0 MIT (").unwrap_err();
+        assert!(errors.len() >= 2);
+    }
+
+    #[test]
+    fn parse_delegates_to_first_error() {
+        let recovering = Expression::parse_recovering("MIT MIT").unwrap_err();
+        let single = Expression::parse("MIT MIT").unwrap_err();
+        assert_eq!(single.span, recovering[0].span);
+    }
+}